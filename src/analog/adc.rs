@@ -1,9 +1,31 @@
 //! # Analog to Digital converter
+use core::cell::Cell;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+
+use cortex_m::interrupt::Mutex;
+
+use crate::dma;
 use crate::gpio::*;
 use crate::rcc::Rcc;
 use crate::stm32::ADC;
 use hal::adc::{Channel, OneShot};
 
+static WAKER: Mutex<Cell<Option<Waker>>> = Mutex::new(Cell::new(None));
+static CONVERSION_RESULT: Mutex<Cell<Option<u16>>> = Mutex::new(Cell::new(None));
+
+/// Address of the factory VREFINT calibration value: a 12-bit, right-aligned VREFINT reading
+/// taken at VDDA = 3.0 V, 30 °C.
+const VREFINT_CAL: *const u16 = 0x1FFF_75AA as *const u16;
+
+/// Address of the factory temperature-sensor calibration reading taken at 30 °C, VDDA = 3.0 V.
+const TS_CAL1: *const u16 = 0x1FFF_75A8 as *const u16;
+/// Address of the factory temperature-sensor calibration reading taken at 130 °C, VDDA = 3.0 V.
+const TS_CAL2: *const u16 = 0x1FFF_75CA as *const u16;
+const TS_CAL1_TEMP: i32 = 30;
+const TS_CAL2_TEMP: i32 = 130;
+
 /// ADC Result Alignment
 #[derive(PartialEq)]
 pub enum Align {
@@ -20,6 +42,15 @@ pub enum Align {
     Left,
 }
 
+/// Scan direction for a multi-channel DMA-driven [`Adc::read_sequence()`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ScanDirection {
+    /// Convert the sequence once, fill the buffer and stop.
+    OneShot,
+    /// Continuously restart the sequence, overwriting the buffer with the latest samples.
+    Circular,
+}
+
 /// ADC Sampling Precision
 #[derive(Copy, Clone, PartialEq)]
 pub enum Precision {
@@ -33,6 +64,18 @@ pub enum Precision {
     B_6 = 0b11,
 }
 
+impl Precision {
+    /// Returns the maximum raw right-aligned reading for this precision (all bits set).
+    pub fn max_count(self) -> u16 {
+        match self {
+            Precision::B_12 => 4095,
+            Precision::B_10 => 1023,
+            Precision::B_8 => 255,
+            Precision::B_6 => 63,
+        }
+    }
+}
+
 /// ADC Sampling time
 #[derive(Copy, Clone, PartialEq)]
 pub enum SampleTime {
@@ -72,6 +115,19 @@ pub enum AsyncClockDiv {
     AsyncD256 = 8,
 }
 
+/// Hardware oversampling ratio: the number of consecutive samples averaged into one result.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OversamplingRatio {
+    X2 = 0b000,
+    X4 = 0b001,
+    X8 = 0b010,
+    X16 = 0b011,
+    X32 = 0b100,
+    X64 = 0b101,
+    X128 = 0b110,
+    X256 = 0b111,
+}
+
 /// Analog to Digital converter interface
 pub struct Adc {
     rb: ADC,
@@ -163,7 +219,59 @@ impl Adc {
         self.precision = precision;
     }
 
+    /// Enables hardware oversampling: `ratio` consecutive samples are accumulated and the sum
+    /// is right-shifted by `shift` bits (0-8).
+    ///
+    /// A 12-bit conversion oversampled x16 with `shift = 4` returns a stable 12-bit result with
+    /// reduced noise. A smaller shift widens the value returned via `DR` beyond the configured
+    /// [`Precision`] (e.g. x16 with `shift = 0` at 12-bit precision returns a 16-bit-wide
+    /// accumulator), so callers feeding an oversampled reading into [`Self::to_millivolts()`]
+    /// must account for the extra width. Pairs naturally with [`Self::read_vdda()`] and
+    /// [`Self::read_temperature()`], where the reduced noise materially improves accuracy.
+    ///
+    /// `DR` is a 16-bit register, so `precision_bits + log2(ratio) - shift` must not exceed 16:
+    /// oversampling only ever trades ratio for noise reduction within that 16-bit width, it
+    /// never widens the readable result past it. A ratio/shift combination that would need
+    /// more than 16 bits (e.g. x256 with no shift, which would need 20) cannot be retrieved
+    /// through `DR` at all and is rejected below rather than silently truncated.
+    pub fn set_oversampling(&mut self, ratio: OversamplingRatio, shift: u8) {
+        assert!(shift <= 8);
+
+        let precision_bits: i16 = match self.precision {
+            Precision::B_12 => 12,
+            Precision::B_10 => 10,
+            Precision::B_8 => 8,
+            Precision::B_6 => 6,
+        };
+        let oversampled_bits = ratio as i16 + 1;
+        assert!(
+            precision_bits + oversampled_bits - shift as i16 <= 16,
+            "oversampled result would overflow the 16-bit DR register"
+        );
+
+        self.rb.cfgr2.modify(|_, w| unsafe {
+            w.ovsr()
+                .bits(ratio as u8)
+                .ovss()
+                .bits(shift)
+                .ovse()
+                .set_bit()
+        });
+    }
+
+    /// Disables hardware oversampling.
+    pub fn disable_oversampling(&mut self) {
+        self.rb.cfgr2.modify(|_, w| w.ovse().clear_bit());
+    }
+
     fn power_up(&mut self) {
+        // ADRDY is only re-raised by hardware on the rising edge of ADEN, so if the ADC is
+        // already enabled (e.g. left powered between interrupt-driven conversions), clearing
+        // it here and waiting for it to come back would hang forever.
+        if self.rb.cr.read().aden().bit_is_set() {
+            return;
+        }
+
         self.rb.isr.modify(|_, w| w.adrdy().set_bit());
         self.rb.cr.modify(|_, w| w.aden().set_bit());
         while self.rb.isr.read().adrdy().bit_is_clear() {}
@@ -175,9 +283,175 @@ impl Adc {
         while self.rb.cr.read().aden().bit_is_set() {}
     }
 
+    /// Disables the ADC, powering it down.
+    ///
+    /// Conversions driven through [`Self::start_conversion()`]/[`Self::on_interrupt()`] leave
+    /// the ADC enabled between conversions so back-to-back interrupt-driven reads don't pay
+    /// the power-up/power-down cost (and latency) on every sample; call this from a non-ISR
+    /// context once the caller is done taking background conversions.
+    pub fn stop(&mut self) {
+        self.power_down();
+    }
+
+    /// Corrects a raw `DR` reading for the current [`Align`]/[`Precision`].
+    ///
+    /// Left-aligned 6-bit results are placed in bits `[7:2]` of `DR` rather than in the top
+    /// bits like every other precision, so they need an extra 8-bit left-shift to land in the
+    /// documented 0-65535 range.
+    fn align_result(&self, res: u16) -> u16 {
+        if self.align == Align::Left && self.precision == Precision::B_6 {
+            res << 8
+        } else {
+            res
+        }
+    }
+
     pub fn release(self) -> ADC {
         self.rb
     }
+
+    /// Configures the analog watchdog to monitor a single channel.
+    ///
+    /// The watchdog continuously compares every conversion on `PIN` against the thresholds
+    /// set with [`Self::set_watchdog_thresholds()`] and sets `ISR.AWD1` the moment a result
+    /// falls outside of them, without needing an explicit [`OneShot::read()`]. Poll
+    /// [`Self::watchdog_triggered()`] or call [`Self::enable_watchdog_interrupt()`] to be
+    /// notified as soon as it trips.
+    pub fn enable_analog_watchdog<PIN>(&mut self, _pin: &mut PIN)
+    where
+        PIN: Channel<Adc, ID = u8>,
+    {
+        self.rb.cfgr1.modify(|_, w| unsafe {
+            w.awd1ch()
+                .bits(PIN::channel())
+                .awd1sgl()
+                .set_bit()
+                .awd1en()
+                .set_bit()
+        });
+    }
+
+    /// Configures the analog watchdog to monitor every channel selected in `CHSELR`.
+    pub fn enable_analog_watchdog_all(&mut self) {
+        self.rb
+            .cfgr1
+            .modify(|_, w| w.awd1sgl().clear_bit().awd1en().set_bit());
+    }
+
+    /// Disables the analog watchdog.
+    pub fn disable_analog_watchdog(&mut self) {
+        self.rb.cfgr1.modify(|_, w| w.awd1en().clear_bit());
+    }
+
+    /// Sets the analog watchdog low/high thresholds.
+    ///
+    /// `TR1.LTR`/`HTR` are always raw 12-bit right-aligned comparison values, regardless of
+    /// the [`Precision`]/[`Align`] currently configured for conversions: the watchdog compares
+    /// against the unshifted, unscaled 12-bit ADC result, never the caller-facing value
+    /// produced by [`OneShot::read()`] or [`Self::to_millivolts()`].
+    pub fn set_watchdog_thresholds(&mut self, low: u16, high: u16) {
+        self.rb
+            .tr1
+            .modify(|_, w| unsafe { w.ltr().bits(low).htr().bits(high) });
+    }
+
+    /// Unmasks the `AWD1` interrupt, so a watchdog trip also raises the ADC interrupt.
+    pub fn enable_watchdog_interrupt(&mut self) {
+        self.rb.ier.modify(|_, w| w.awdie().set_bit());
+    }
+
+    /// Masks the `AWD1` interrupt.
+    pub fn disable_watchdog_interrupt(&mut self) {
+        self.rb.ier.modify(|_, w| w.awdie().clear_bit());
+    }
+
+    /// Returns whether the analog watchdog has tripped, clearing `ISR.AWD1` if it has.
+    pub fn watchdog_triggered(&mut self) -> bool {
+        let triggered = self.rb.isr.read().awd1().bit_is_set();
+        if triggered {
+            self.rb.isr.modify(|_, w| w.awd1().set_bit());
+        }
+        triggered
+    }
+
+    /// Starts a conversion on `PIN` and unmasks the end-of-conversion interrupt, returning
+    /// immediately instead of busy-waiting like [`OneShot::read()`].
+    ///
+    /// Poll, or `.await`, the returned [`Conversion`] to obtain the result once it is ready;
+    /// the CPU is free to sleep (e.g. `WFI`) in the meantime. [`Self::on_interrupt()`] must be
+    /// called from the ADC interrupt handler to drive the conversion to completion.
+    pub fn start_conversion<PIN>(&mut self, _pin: &mut PIN) -> Conversion
+    where
+        PIN: Channel<Adc, ID = u8>,
+    {
+        self.power_up();
+        self.rb.cfgr1.modify(|_, w| unsafe {
+            w.res()
+                .bits(self.precision as u8)
+                .align()
+                .bit(self.align == Align::Left)
+        });
+
+        self.rb
+            .smpr
+            .modify(|_, w| unsafe { w.smp1().bits(self.sample_time as u8) });
+
+        self.rb
+            .chselr()
+            .modify(|_, w| unsafe { w.chsel().bits(1 << PIN::channel()) });
+
+        cortex_m::interrupt::free(|cs| CONVERSION_RESULT.borrow(cs).set(None));
+
+        self.rb.isr.modify(|_, w| w.eos().set_bit());
+        self.rb.ier.modify(|_, w| w.eosie().set_bit());
+        self.rb.cr.modify(|_, w| w.adstart().set_bit());
+
+        Conversion { _private: () }
+    }
+
+    /// Services an end-of-conversion interrupt started by [`Self::start_conversion()`].
+    ///
+    /// Call this from the ADC interrupt handler: it reads `DR`, clears `ISR.EOS`, masks the
+    /// end-of-conversion interrupt again and wakes whoever is polling the pending
+    /// [`Conversion`]. The ADC is left enabled so the handler stays O(1) — powering it down
+    /// busy-waits on `ADDIS`/`ADEN`, which has no place in an interrupt handler and would
+    /// reintroduce the blocking this request was meant to remove. Call [`Self::stop()`] from a
+    /// non-ISR context once background conversions are no longer needed.
+    pub fn on_interrupt(&mut self) {
+        if self.rb.isr.read().eos().bit_is_set() {
+            let res = self.rb.dr.read().bits() as u16;
+            let res = self.align_result(res);
+            self.rb.isr.modify(|_, w| w.eos().set_bit());
+            self.rb.ier.modify(|_, w| w.eosie().clear_bit());
+
+            cortex_m::interrupt::free(|cs| {
+                CONVERSION_RESULT.borrow(cs).set(Some(res));
+                if let Some(waker) = WAKER.borrow(cs).take() {
+                    waker.wake();
+                }
+            });
+        }
+    }
+}
+
+/// A conversion started by [`Adc::start_conversion()`], pending completion.
+pub struct Conversion {
+    _private: (),
+}
+
+impl Future for Conversion {
+    type Output = u16;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        cortex_m::interrupt::free(|cs| {
+            if let Some(res) = CONVERSION_RESULT.borrow(cs).take() {
+                Poll::Ready(res)
+            } else {
+                WAKER.borrow(cs).set(Some(cx.waker().clone()));
+                Poll::Pending
+            }
+        })
+    }
 }
 
 pub trait AdcExt {
@@ -219,11 +493,7 @@ where
         while self.rb.isr.read().eos().bit_is_clear() {}
 
         let res = self.rb.dr.read().bits() as u16;
-        let val = if self.align == Align::Left && self.precision == Precision::B_6 {
-            res << 8
-        } else {
-            res
-        };
+        let val = self.align_result(res);
 
         self.power_down();
         Ok(val.into())
@@ -284,6 +554,204 @@ int_adc! {
     VBat: (14, vbaten),
 }
 
+impl Adc {
+    /// Measures VDDA in millivolts using the factory-trimmed `VREFINT_CAL` calibration value.
+    ///
+    /// The ADC has no factory gain/offset calibration, and VDDA drift directly scales every
+    /// reading, so this is the basis for turning raw counts into absolute voltages via
+    /// [`Self::to_millivolts()`]. Temporarily forces 12-bit right-aligned precision to take
+    /// the reading, then restores the previously configured precision and alignment.
+    pub fn read_vdda(&mut self) -> u16 {
+        let mut vref = VRef::new();
+        vref.enable(self);
+
+        let prev_precision = self.precision;
+        let prev_align = core::mem::replace(&mut self.align, Align::Right);
+        self.precision = Precision::B_12;
+
+        let vrefint_data: u16 = nb::block!(self.read(&mut vref)).unwrap();
+
+        self.precision = prev_precision;
+        self.align = prev_align;
+        vref.disable(self);
+
+        let vrefint_cal = unsafe { core::ptr::read(VREFINT_CAL) };
+        (3000u32 * vrefint_cal as u32 / vrefint_data as u32) as u16
+    }
+
+    /// Converts a raw reading taken at the ADC's current precision/alignment to millivolts,
+    /// scaling it by [`Self::read_vdda()`] and the configuration's full-scale count.
+    pub fn to_millivolts(&mut self, raw: u16) -> u16 {
+        let max_count = match self.align {
+            Align::Right => self.precision.max_count(),
+            Align::Left => u16::MAX,
+        };
+        let vdda_mv = self.read_vdda();
+
+        (raw as u32 * vdda_mv as u32 / max_count as u32) as u16
+    }
+
+    /// Reads the internal temperature sensor and returns the die temperature in degrees
+    /// Celsius.
+    ///
+    /// Uses the factory `TS_CAL1`/`TS_CAL2` calibration points, taken at VDDA = 3.0 V, together
+    /// with [`Self::read_vdda()`] to rescale the sample to that reference before linearly
+    /// interpolating between the two calibration temperatures. The sensor needs the longest
+    /// sampling time to settle, so the configured [`SampleTime`] is temporarily forced to
+    /// [`SampleTime::T_160`].
+    pub fn read_temperature(&mut self) -> i16 {
+        let mut vtemp = VTemp::new();
+        vtemp.enable(self);
+
+        let prev_precision = self.precision;
+        let prev_align = core::mem::replace(&mut self.align, Align::Right);
+        let prev_sample_time = self.sample_time;
+        self.precision = Precision::B_12;
+        self.sample_time = SampleTime::T_160;
+
+        let vdda_mv = self.read_vdda();
+        let ts_data: u16 = nb::block!(self.read(&mut vtemp)).unwrap();
+
+        self.precision = prev_precision;
+        self.align = prev_align;
+        self.sample_time = prev_sample_time;
+        vtemp.disable(self);
+
+        let ts_cal1 = unsafe { core::ptr::read(TS_CAL1) } as i32;
+        let ts_cal2 = unsafe { core::ptr::read(TS_CAL2) } as i32;
+        let ts_data_scaled = ts_data as i32 * vdda_mv as i32 / 3000;
+
+        (((TS_CAL2_TEMP - TS_CAL1_TEMP) * (ts_data_scaled - ts_cal1)) / (ts_cal2 - ts_cal1)
+            + TS_CAL1_TEMP) as i16
+    }
+
+    /// Starts a DMA-driven scan across multiple channels, streaming each `DR` result into
+    /// `buffer` as `channels` is converted in sequence.
+    ///
+    /// In [`ScanDirection::OneShot`] the sequence converts once and stops; in
+    /// [`ScanDirection::Circular`] it restarts automatically so `buffer` always holds the most
+    /// recent sample of every channel. This is the way to sample several sensors (e.g. a bank
+    /// of thermistors or current shunts) at a fixed cadence without CPU intervention per
+    /// sample.
+    ///
+    /// The hardware always scans in ascending channel-number order (`SCANDIR` upward), so
+    /// `channels` must already be sorted ascending: `buffer[i]` ends up holding the sample for
+    /// `channels[i]`, not for whatever order `channels` was given in. `buffer` and `channels`
+    /// must be the same length.
+    pub fn read_sequence<CHANNEL>(
+        mut self,
+        channels: &[u8],
+        buffer: &'static mut [u16],
+        direction: ScanDirection,
+        mut dma_channel: CHANNEL,
+    ) -> ScanDma<CHANNEL>
+    where
+        CHANNEL: dma::Channel,
+    {
+        assert_eq!(channels.len(), buffer.len());
+        assert!(
+            channels.windows(2).all(|w| w[0] < w[1]),
+            "channels must be sorted in ascending order to match the hardware scan direction"
+        );
+
+        self.power_up();
+        self.rb.cfgr1.modify(|_, w| unsafe {
+            w.res()
+                .bits(self.precision as u8)
+                .align()
+                .bit(self.align == Align::Left)
+                .cont()
+                .bit(direction == ScanDirection::Circular)
+                .scandir()
+                .clear_bit()
+        });
+
+        self.rb
+            .smpr
+            .modify(|_, w| unsafe { w.smp1().bits(self.sample_time as u8) });
+
+        let mask = channels.iter().fold(0u32, |mask, chan| mask | (1 << chan));
+        self.rb.chselr().modify(|_, w| unsafe { w.chsel().bits(mask) });
+
+        dma_channel.set_peripheral_address(self.rb.dr.as_ptr() as u32, false);
+        dma_channel.set_memory_address(buffer.as_ptr() as u32, true);
+        dma_channel.set_transfer_length(buffer.len() as u16);
+        dma_channel.set_circular(direction == ScanDirection::Circular);
+
+        self.rb.cfgr1.modify(|_, w| {
+            w.dmaen()
+                .set_bit()
+                .dmacfg()
+                .bit(direction == ScanDirection::Circular)
+        });
+
+        dma_channel.start();
+        self.rb.cr.modify(|_, w| w.adstart().set_bit());
+
+        ScanDma {
+            adc: self,
+            dma_channel,
+            direction,
+        }
+    }
+}
+
+/// A multi-channel scan conversion in progress, started by [`Adc::read_sequence()`].
+pub struct ScanDma<CHANNEL> {
+    adc: Adc,
+    dma_channel: CHANNEL,
+    direction: ScanDirection,
+}
+
+impl<CHANNEL> ScanDma<CHANNEL>
+where
+    CHANNEL: dma::Channel,
+{
+    /// Returns `true` once a [`ScanDirection::OneShot`] scan has filled the buffer.
+    ///
+    /// A [`ScanDirection::Circular`] scan never completes on its own; use [`Self::stop()`] to
+    /// end it.
+    pub fn is_complete(&self) -> bool {
+        self.direction == ScanDirection::OneShot && !self.dma_channel.is_enabled()
+    }
+
+    /// Blocks until a [`ScanDirection::OneShot`] scan completes, then returns the ADC and DMA
+    /// channel for reuse.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called on a [`ScanDirection::Circular`] scan, which never completes on its
+    /// own and would otherwise hang here forever; call [`Self::stop()`] instead.
+    pub fn wait(mut self) -> (Adc, CHANNEL) {
+        assert!(
+            self.direction == ScanDirection::OneShot,
+            "wait() on a circular scan never completes; call stop() instead"
+        );
+        while !self.is_complete() {}
+        self.teardown();
+        (self.adc, self.dma_channel)
+    }
+
+    /// Stops a [`ScanDirection::Circular`] scan and returns the ADC and DMA channel for reuse.
+    pub fn stop(mut self) -> (Adc, CHANNEL) {
+        self.dma_channel.disable();
+        self.teardown();
+        (self.adc, self.dma_channel)
+    }
+
+    fn teardown(&mut self) {
+        self.adc.rb.cfgr1.modify(|_, w| w.dmaen().clear_bit());
+
+        // `ADDIS` is only honored while `ADSTART` is clear; a circular scan leaves `ADSTART`
+        // set indefinitely, so request a stop and wait for it before powering down, or
+        // `power_down()`'s busy-wait would spin forever.
+        self.adc.rb.cr.modify(|_, w| w.adstp().set_bit());
+        while self.adc.rb.cr.read().adstart().bit_is_set() {}
+
+        self.adc.power_down();
+    }
+}
+
 adc_pin! {
     Channel0: (gpioa::PA0<Analog>, 0u8),
     Channel1: (gpioa::PA1<Analog>, 1u8),